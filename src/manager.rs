@@ -8,19 +8,35 @@
 use std::{
     collections::{HashMap, HashSet},
     hash::Hash,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use futures::{
     channel::mpsc, future::BoxFuture, stream::FuturesUnordered, Future, FutureExt, Stream,
     StreamExt,
 };
+use rand::Rng;
+use tokio::sync::Semaphore;
 
-use crate::{signal::StopListener, StopBroadcaster, Task};
+use crate::{
+    signal::{StopListener, StopToken},
+    StopBroadcaster, Task,
+};
 
 /// Tracks tasks at the global conductor level, as well as each individual cell level.
 pub struct TaskManager<GroupKey, Outcome> {
     groups: HashMap<GroupKey, TaskGroup>,
     children: HashMap<GroupKey, HashSet<GroupKey>>,
+    /// Explicit parent of each group that has been touched, superseding
+    /// `parent_map` once recorded. Kept in sync with `children` and mutated
+    /// directly by `add_group`/`reparent`, rather than purely derived from
+    /// `parent_map` on first use.
+    parents: HashMap<GroupKey, GroupKey>,
+    closed: HashSet<GroupKey>,
     parent_map: Box<dyn 'static + Send + Sync + Fn(&GroupKey) -> Option<GroupKey>>,
     outcomes: mpsc::Sender<(GroupKey, Outcome)>,
 }
@@ -37,28 +53,183 @@ where
         Self {
             groups: Default::default(),
             children: Default::default(),
+            parents: Default::default(),
+            closed: Default::default(),
             parent_map: Box::new(parent_map),
             outcomes,
         }
     }
 
-    /// Add a task to a group
+    /// Explicitly register a group's parent, creating the group if it doesn't
+    /// already exist. This lets topology be built up at runtime instead of
+    /// being fixed by the `parent_map` closure passed to `new` -- e.g. for
+    /// cells that only learn their parent conductor after spawning.
+    ///
+    /// Fails if `parent` is a descendant of `key` (which would create a
+    /// cycle); the group is still created either way.
+    pub fn add_group(
+        &mut self,
+        key: GroupKey,
+        parent: Option<GroupKey>,
+    ) -> Result<(), ReparentError> {
+        self.groups.entry(key.clone()).or_insert_with(TaskGroup::new);
+        self.reparent(key, parent)
+    }
+
+    /// Move a group (and its entire subtree) to a new parent, or detach it to
+    /// the root if `new_parent` is `None`. Rejects cycles: `new_parent` must
+    /// not be `key` itself or a descendant of `key`.
+    pub fn reparent(
+        &mut self,
+        key: GroupKey,
+        new_parent: Option<GroupKey>,
+    ) -> Result<(), ReparentError> {
+        if let Some(new_parent) = &new_parent {
+            if *new_parent == key {
+                return Err(ReparentError::Cycle);
+            }
+            let mut ancestor = self.parent_of(new_parent);
+            while let Some(a) = ancestor {
+                if a == key {
+                    return Err(ReparentError::Cycle);
+                }
+                ancestor = self.parent_of(&a);
+            }
+        }
+
+        if let Some(old_parent) = self.parents.remove(&key) {
+            if let Some(siblings) = self.children.get_mut(&old_parent) {
+                siblings.remove(&key);
+            }
+        }
+
+        if let Some(new_parent) = new_parent {
+            self.children
+                .entry(new_parent.clone())
+                .or_insert_with(HashSet::new)
+                .insert(key.clone());
+            self.parents.insert(key, new_parent);
+        }
+
+        Ok(())
+    }
+
+    /// Mark a group closed: no further tasks may be added to it, or to any of
+    /// its descendants, via `add_task`/`add_supervised_task`. Tasks already
+    /// running are left alone; use `join_group` to wait for them to finish on
+    /// their own, without forcing a stop the way `stop_group` does.
+    pub fn close_group(&mut self, key: &GroupKey) {
+        self.closed.extend(self.descendants(key));
+    }
+
+    /// Whether `key` or any of its ancestors has been closed via `close_group`.
+    pub fn is_closed(&self, key: &GroupKey) -> bool {
+        if self.closed.contains(key) {
+            return true;
+        }
+        let mut ancestor = self.parent_of(key);
+        while let Some(a) = ancestor {
+            if self.closed.contains(&a) {
+                return true;
+            }
+            ancestor = self.parent_of(&a);
+        }
+        false
+    }
+
+    /// A group's current parent: the explicitly-registered one if present
+    /// (via `add_group`/`reparent`), otherwise whatever the static
+    /// `parent_map` closure reports.
+    fn parent_of(&self, key: &GroupKey) -> Option<GroupKey> {
+        self.parents
+            .get(key)
+            .cloned()
+            .or_else(|| (self.parent_map)(key))
+    }
+
+    /// Get a cloneable, poll-based [`StopToken`] for a group, for tasks that
+    /// want to check cancellation inside a loop rather than `select`ing on a
+    /// [`StopListener`]. A token observes cancellation from any ancestor
+    /// group as well, since `stop_group` signals every descendant's own
+    /// broadcaster directly.
+    pub fn stop_token(&mut self, key: GroupKey) -> StopToken {
+        self.group(key).stopper.token()
+    }
+
+    /// Wait for all tasks currently running in a group and its descendants to
+    /// finish on their own. Unlike `stop_group`, this never signals the tasks
+    /// to stop; it's meant to be paired with `close_group` for a graceful
+    /// "stop accepting work, then drain" shutdown.
+    pub fn join_group(&mut self, key: &GroupKey) -> GroupStop {
+        let mut tasks = vec![];
+        for key in self.descendants(key) {
+            if let Some(group) = self.groups.remove(&key) {
+                tasks.push(group.tasks.collect::<Vec<_>>());
+            }
+        }
+
+        futures::future::join_all(tasks).map(|_| ()).boxed()
+    }
+
+    /// Add a task to a group. A no-op if the group (or an ancestor) has been
+    /// closed via `close_group`.
     pub fn add_task<Fut: Future<Output = Outcome> + Send + 'static>(
         &mut self,
         key: GroupKey,
         f: impl FnOnce(StopListener) -> Fut + Send + 'static,
     ) {
+        if self.is_closed(&key) {
+            return;
+        }
         let mut tx = self.outcomes.clone();
         let group = self.group(key.clone());
-        let listener = group.stopper.listener();
-        let task = async move {
+        let stopper = group.stopper.clone();
+        let max_concurrent = group.max_concurrent.clone();
+        let running = group.running.clone();
+        // Run the task as its own tokio task so that its `AbortHandle` can be
+        // retained on the group, letting `stop_group_deadline` force it to end
+        // if it doesn't cooperate with its `StopListener` in time.
+        let handle = tokio::spawn(async move {
+            let mut listener = stopper.listener().await;
+            let mut permit = None;
+            if let Some(sem) = max_concurrent {
+                tokio::select! {
+                    p = sem.acquire_owned() => permit = Some(p.expect("semaphore is never closed")),
+                    // Stopped while queued for a permit: abandon the acquire
+                    // rather than let it block shutdown.
+                    _ = &mut listener => return None,
+                }
+            }
+            running.fetch_add(1, Ordering::SeqCst);
             let outcome = f(listener).await;
-            tx.try_send((key, outcome)).ok();
+            running.fetch_sub(1, Ordering::SeqCst);
+            drop(permit);
+            Some(outcome)
+        });
+        group.abort_handles.lock().unwrap().push(handle.abort_handle());
+        // Forward the outcome from its own spawned task, the same as
+        // `add_supervised_task`'s supervisor, so it's delivered as soon as
+        // the task finishes rather than only once the group is torn down or
+        // joined -- `group.tasks` is otherwise never polled on its own.
+        let forward = tokio::spawn(async move {
+            if let Ok(Some(outcome)) = handle.await {
+                tx.try_send((key, outcome)).ok();
+            }
+        });
+        let task = async move {
+            forward.await.ok();
         }
         .boxed();
         group.tasks.push(task);
     }
 
+    /// Cap how many of a group's tasks may run concurrently; the rest queue,
+    /// waiting for a permit to free up. Pass `None` to remove the cap.
+    /// Tasks already queued against a previous cap are unaffected.
+    pub fn set_max_concurrent(&mut self, key: GroupKey, max_concurrent: Option<usize>) {
+        self.group(key).max_concurrent = max_concurrent.map(|n| Arc::new(Semaphore::new(n)));
+    }
+
     pub fn num_tasks(&self, key: &GroupKey) -> usize {
         self.groups
             .get(key)
@@ -66,6 +237,21 @@ where
             .unwrap_or_default()
     }
 
+    /// Number of a group's tasks currently executing (i.e. past any
+    /// `max_concurrent` queue).
+    pub fn num_running(&self, key: &GroupKey) -> usize {
+        self.groups
+            .get(key)
+            .map(|group| group.running.load(Ordering::SeqCst) as usize)
+            .unwrap_or_default()
+    }
+
+    /// Number of a group's tasks still queued, waiting for a permit under
+    /// `max_concurrent`.
+    pub fn num_queued(&self, key: &GroupKey) -> usize {
+        self.num_tasks(key).saturating_sub(self.num_running(key))
+    }
+
     /// Remove a group, returning the group as a stream which produces
     /// all task results in the order they resolve.
     pub fn stop_group(&mut self, key: &GroupKey) -> GroupStop {
@@ -81,6 +267,72 @@ where
         futures::future::join_all(tasks).map(|_| ()).boxed()
     }
 
+    /// Like [`Self::stop_group`], but a task that hasn't drained within
+    /// `deadline` after the stop signal is force-aborted via its `AbortHandle`
+    /// rather than letting a single misbehaving task wedge the whole shutdown.
+    pub fn stop_group_deadline(
+        &mut self,
+        key: &GroupKey,
+        deadline: Duration,
+    ) -> GroupStopDeadline<GroupKey> {
+        let keys = self.descendants(key);
+        self.stop_keys_deadline(keys, deadline)
+    }
+
+    /// Like [`Self::stop_group_deadline`], but stops every group the manager
+    /// currently knows about.
+    pub fn stop_all_deadline(&mut self, deadline: Duration) -> GroupStopDeadline<GroupKey> {
+        let keys = self.groups.keys().cloned().collect();
+        self.stop_keys_deadline(keys, deadline)
+    }
+
+    fn stop_keys_deadline(
+        &mut self,
+        keys: HashSet<GroupKey>,
+        deadline: Duration,
+    ) -> GroupStopDeadline<GroupKey> {
+        let mut drains = vec![];
+        for key in keys {
+            if let Some(mut group) = self.groups.remove(&key) {
+                group.stopper.emit();
+                let abort_handles = std::mem::take(&mut *group.abort_handles.lock().unwrap());
+                drains.push((key, group.tasks, abort_handles));
+            }
+        }
+
+        async move {
+            let mut summary = GroupStopSummary::default();
+            let outcomes = futures::future::join_all(drains.into_iter().map(
+                |(key, mut tasks, abort_handles)| async move {
+                    let drained =
+                        tokio::time::timeout(deadline, tasks.by_ref().collect::<Vec<_>>())
+                            .await
+                            .is_ok();
+                    if !drained {
+                        for handle in &abort_handles {
+                            handle.abort();
+                        }
+                        // Give aborted tasks a moment to unwind so their
+                        // `AbortHandle`s don't dangle past this call.
+                        tasks.collect::<Vec<_>>().await;
+                    }
+                    (key, drained)
+                },
+            ))
+            .await;
+
+            for (key, drained) in outcomes {
+                if drained {
+                    summary.completed.insert(key);
+                } else {
+                    summary.aborted.insert(key);
+                }
+            }
+            summary
+        }
+        .boxed()
+    }
+
     pub(crate) fn descendants(&self, key: &GroupKey) -> HashSet<GroupKey> {
         let mut all = HashSet::new();
         all.insert(key.clone());
@@ -97,23 +349,254 @@ where
     }
 
     fn group(&mut self, key: GroupKey) -> &mut TaskGroup {
+        // `stop_group`/`join_group`/`stop_group_deadline` remove a key's
+        // `TaskGroup` from `self.groups` without touching `self.parents`/
+        // `self.children`, so a later re-touch of an explicitly-placed group
+        // (e.g. via `add_group`/`reparent`) must not re-derive its parent
+        // from the original `parent_map` -- that would insert a stale edge
+        // and clobber the explicit topology back to the static one.
+        let has_explicit_parent = self.parents.contains_key(&key);
         self.groups.entry(key.clone()).or_insert_with(|| {
-            if let Some(parent) = (self.parent_map)(&key) {
-                self.children
-                    .entry(parent)
-                    .or_insert_with(HashSet::new)
-                    .insert(key);
+            if !has_explicit_parent {
+                if let Some(parent) = (self.parent_map)(&key) {
+                    self.children
+                        .entry(parent.clone())
+                        .or_insert_with(HashSet::new)
+                        .insert(key.clone());
+                    self.parents.insert(key.clone(), parent);
+                }
             }
             TaskGroup::new()
         })
     }
 }
 
+impl<GroupKey, Outcome> TaskManager<GroupKey, Outcome>
+where
+    GroupKey: Clone + Eq + Hash + Send + 'static,
+    Outcome: Default + Send + 'static,
+{
+    /// Add a task which is supervised: whenever it returns or panics, it is
+    /// re-invoked with a fresh [`StopListener`] according to `policy`, until the
+    /// group is stopped. This is the complement to the group hierarchy itself:
+    /// groups supervise their descendants, and supervised tasks let individual
+    /// cells restart themselves.
+    ///
+    /// `f` is called repeatedly, so it must be `Fn` rather than `FnOnce`. If the
+    /// restart budget in `policy` is exhausted, the most recent `Outcome` is sent
+    /// to the outcomes channel as usual; if the task was mid-panic at that point
+    /// (so no `Outcome` was ever produced), `Outcome::default()` is sent instead.
+    pub fn add_supervised_task<Fut: Future<Output = Outcome> + Send + 'static>(
+        &mut self,
+        key: GroupKey,
+        policy: RestartPolicy,
+        f: impl Fn(StopListener) -> Fut + Send + Sync + 'static,
+    ) {
+        if self.is_closed(&key) {
+            return;
+        }
+        let mut tx = self.outcomes.clone();
+        let group = self.group(key.clone());
+        let stopper = group.stopper.clone();
+        let abort_handles = group.abort_handles.clone();
+        let f = Arc::new(f);
+        // Run the supervisor loop as its own tokio task, the same way
+        // `add_task` spawns its work eagerly, rather than pushing the live
+        // loop straight into `group.tasks`: that `FuturesUnordered` is only
+        // ever polled by `stop_group`/`join_group` and friends, so a pushed
+        // (not spawned) future would never run its restart loop until the
+        // group was torn down.
+        let supervisor = tokio::spawn(async move {
+            // Held for the lifetime of the supervisor so that a stop signal is
+            // observed no matter which restart attempt is currently in flight.
+            let mut stopping = stopper.listener().await;
+            let mut attempt: u32 = 0;
+            let mut restarts: u32 = 0;
+
+            loop {
+                let listener = stopper.listener().await;
+                let started = Instant::now();
+                let handle = tokio::spawn({
+                    let f = f.clone();
+                    async move { f(listener).await }
+                });
+                // Registered on the group so `stop_group_deadline`/
+                // `stop_all_deadline` can force-abort a misbehaving attempt
+                // that ignores its `StopListener`, the same as a plain task.
+                abort_handles.lock().unwrap().push(handle.abort_handle());
+                tokio::pin!(handle);
+
+                let mut is_stopping = false;
+                let result = tokio::select! {
+                    res = &mut handle => res,
+                    _ = &mut stopping => {
+                        // The attempt's own listener already saw the same
+                        // broadcast, so just wait for it to wind down (or be
+                        // force-aborted via `abort_handles` above).
+                        is_stopping = true;
+                        handle.await
+                    },
+                };
+
+                if started.elapsed() >= policy.reset_after {
+                    attempt = 0;
+                }
+                let (outcome, exited_with_error) = match result {
+                    Ok(outcome) => (Some(outcome), false),
+                    Err(_join_err) => (None, true),
+                };
+
+                let restart_eligible = policy.on == RestartOn::Always || exited_with_error;
+                let budget_exhausted = policy.max_restarts.map_or(false, |max| restarts >= max);
+
+                if is_stopping || !restart_eligible || budget_exhausted {
+                    break outcome.unwrap_or_default();
+                }
+
+                restarts += 1;
+                let delay = policy.backoff(attempt);
+                attempt = attempt.saturating_add(1);
+                // Raced against the stop signal so a restart backoff never
+                // holds up shutdown by as much as `max_delay`.
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {},
+                    _ = &mut stopping => break outcome.unwrap_or_default(),
+                }
+            }
+        });
+
+        let forward = tokio::spawn(async move {
+            if let Ok(outcome) = supervisor.await {
+                tx.try_send((key, outcome)).ok();
+            }
+        });
+        let task = async move {
+            forward.await.ok();
+        }
+        .boxed();
+        group.tasks.push(task);
+    }
+}
+
+/// Whether a supervised task should be restarted after a normal (non-panicking)
+/// return, or only after a panic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestartOn {
+    /// Restart the task whenever it exits, whether it returned normally or panicked.
+    Always,
+    /// Only restart the task if it panicked; a normal return is treated as final.
+    ErrorOnly,
+}
+
+/// Governs how [`TaskManager::add_supervised_task`] restarts a task after it
+/// exits, with exponential backoff between attempts.
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    pub on: RestartOn,
+    /// Delay before the first restart attempt.
+    pub base_delay: Duration,
+    /// Upper bound on the exponential backoff delay, regardless of attempt
+    /// count. Random jitter of up to half that delay is added on top.
+    pub max_delay: Duration,
+    /// If the task stays alive at least this long, the attempt counter resets
+    /// to zero, so a long-lived task doesn't inherit the backoff of its past
+    /// failures.
+    pub reset_after: Duration,
+    /// Maximum number of restarts before giving up and emitting the final
+    /// outcome normally. `None` means restart indefinitely.
+    pub max_restarts: Option<u32>,
+}
+
+impl RestartPolicy {
+    pub fn new(
+        on: RestartOn,
+        base_delay: Duration,
+        max_delay: Duration,
+        reset_after: Duration,
+    ) -> Self {
+        Self {
+            on,
+            base_delay,
+            max_delay,
+            reset_after,
+            max_restarts: None,
+        }
+    }
+
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = Some(max_restarts);
+        self
+    }
+
+    /// `min(base * 2^attempt, max_delay)`, plus random jitter in `[0, delay/2]`
+    /// to avoid a thundering herd of restarts.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let base_ms = self.base_delay.as_millis() as u64;
+        let max_ms = self.max_delay.as_millis() as u64;
+        let delay_ms = base_ms
+            .saturating_mul(1u64 << attempt.min(32))
+            .min(max_ms);
+        let jitter_ms = if delay_ms == 0 {
+            0
+        } else {
+            rand::thread_rng().gen_range(0..=delay_ms / 2)
+        };
+        Duration::from_millis(delay_ms + jitter_ms)
+    }
+}
+
+/// Returned by [`TaskManager::reparent`]/[`TaskManager::add_group`] when the
+/// requested move would create a cycle in the group hierarchy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReparentError {
+    Cycle,
+}
+
+impl std::fmt::Display for ReparentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReparentError::Cycle => write!(f, "reparenting would create a cycle"),
+        }
+    }
+}
+
+impl std::error::Error for ReparentError {}
+
 pub type GroupStop = BoxFuture<'static, ()>;
 
+pub type GroupStopDeadline<GroupKey> = BoxFuture<'static, GroupStopSummary<GroupKey>>;
+
+/// Reports which groups drained within the deadline versus which had to be
+/// force-aborted, so callers can log or alarm on tasks that refused
+/// cooperative shutdown.
+#[derive(Debug)]
+pub struct GroupStopSummary<GroupKey> {
+    /// Groups whose tasks all finished on their own before the deadline.
+    pub completed: HashSet<GroupKey>,
+    /// Groups that still had running tasks at the deadline and were aborted.
+    pub aborted: HashSet<GroupKey>,
+}
+
+impl<GroupKey> Default for GroupStopSummary<GroupKey> {
+    fn default() -> Self {
+        Self {
+            completed: HashSet::new(),
+            aborted: HashSet::new(),
+        }
+    }
+}
+
 struct TaskGroup {
     pub(crate) tasks: FuturesUnordered<Task>,
     pub(crate) stopper: StopBroadcaster,
+    /// Shared so that `add_supervised_task`'s background supervisor loop can
+    /// register each restart attempt's `AbortHandle` as it spawns it, not
+    /// just the handles known synchronously when the group is touched.
+    pub(crate) abort_handles: Arc<Mutex<Vec<tokio::task::AbortHandle>>>,
+    /// Concurrency cap set via `TaskManager::set_max_concurrent`; `None` means
+    /// unlimited.
+    pub(crate) max_concurrent: Option<Arc<Semaphore>>,
+    pub(crate) running: Arc<AtomicU32>,
 }
 
 impl TaskGroup {
@@ -121,6 +604,9 @@ impl TaskGroup {
         Self {
             tasks: FuturesUnordered::new(),
             stopper: StopBroadcaster::new(),
+            abort_handles: Arc::new(Mutex::new(Vec::new())),
+            max_concurrent: None,
+            running: Arc::new(AtomicU32::new(0)),
         }
     }
 }
@@ -272,4 +758,280 @@ mod tests {
             ]
         );
     }
+
+    #[tokio::test]
+    async fn test_supervised_always_restarts_until_budget_exhausted() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let (tx, mut outcomes) = mpsc::channel(1);
+        let mut tm: TaskManager<GroupKey, u32> = TaskManager::new(tx, |_| None);
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let policy = RestartPolicy::new(
+            RestartOn::Always,
+            Duration::from_millis(1),
+            Duration::from_millis(5),
+            Duration::from_secs(60),
+        )
+        .with_max_restarts(2);
+
+        tm.add_supervised_task(GroupKey::A, policy, {
+            let calls = calls.clone();
+            move |_stop| {
+                let calls = calls.clone();
+                async move { calls.fetch_add(1, Ordering::SeqCst) }
+            }
+        });
+
+        let (key, outcome) = outcomes.next().await.unwrap();
+        assert_eq!(key, GroupKey::A);
+        assert_eq!(outcome, 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_supervised_restarts_on_panic_only() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let (tx, mut outcomes) = mpsc::channel(1);
+        let mut tm: TaskManager<GroupKey, u32> = TaskManager::new(tx, |_| None);
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let policy = RestartPolicy::new(
+            RestartOn::ErrorOnly,
+            Duration::from_millis(1),
+            Duration::from_millis(5),
+            Duration::from_secs(60),
+        );
+
+        tm.add_supervised_task(GroupKey::A, policy, {
+            let calls = calls.clone();
+            move |_stop| {
+                let calls = calls.clone();
+                async move {
+                    let n = calls.fetch_add(1, Ordering::SeqCst);
+                    if n < 2 {
+                        panic!("boom");
+                    }
+                    n
+                }
+            }
+        });
+
+        let (_key, outcome) = outcomes.next().await.unwrap();
+        assert_eq!(outcome, 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stop_group_deadline_aborts_stragglers() {
+        use GroupKey::*;
+
+        let (tx, mut outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |g| match g {
+            A => None,
+            B => Some(A),
+            _ => None,
+        });
+
+        tm.add_task(A, |stop| blocker("a1", stop));
+        // Ignores its StopListener entirely, so it can only be removed by abort.
+        tm.add_task(B, |_stop| async {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            "stuck".to_string()
+        });
+
+        let summary = tm
+            .stop_group_deadline(&A, Duration::from_millis(50))
+            .await;
+
+        assert_eq!(summary.completed, hashset! {A});
+        assert_eq!(summary.aborted, hashset! {B});
+        assert_eq!(outcomes.next().await.unwrap(), (A, "a1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_close_and_join_group() {
+        use GroupKey::*;
+
+        let (tx, mut outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |g| match g {
+            A => None,
+            B => Some(A),
+            _ => None,
+        });
+
+        tm.add_task(A, |_stop| async { "a1".to_string() });
+        tm.add_task(B, |_stop| async { "b1".to_string() });
+
+        tm.close_group(&A);
+        assert!(tm.is_closed(&A));
+        assert!(tm.is_closed(&B));
+
+        // Closed groups reject new tasks, silently.
+        tm.add_task(A, |_stop| async { "a2".to_string() });
+        assert_eq!(tm.num_tasks(&A), 1);
+        tm.add_task(B, |_stop| async { "b2".to_string() });
+        assert_eq!(tm.num_tasks(&B), 1);
+
+        // join_group never signals stop, it just waits for the existing tasks.
+        tm.join_group(&A).await;
+
+        assert_eq!(
+            hashset![
+                outcomes.next().await.unwrap(),
+                outcomes.next().await.unwrap(),
+            ],
+            hashset![(A, "a1".to_string()), (B, "b1".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_token_observes_ancestor_stop() {
+        use GroupKey::*;
+
+        let (tx, _outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |g| match g {
+            A => None,
+            B => Some(A),
+            D => Some(B),
+            _ => None,
+        });
+
+        // Creating each group registers its link to its own parent, so touch
+        // every level of the chain to wire up the full A -> B -> D ancestry.
+        tm.stop_token(B);
+        let token = tm.stop_token(D);
+        assert!(!token.is_stopped());
+        assert!(not_ready(token.cancelled()).await);
+
+        tm.stop_group(&A).await;
+
+        assert!(token.is_stopped());
+        assert!(ready(token.cancelled()).await);
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_limits_running() {
+        use tokio::sync::Notify;
+        use GroupKey::*;
+
+        let (tx, mut outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |_| None);
+
+        tm.set_max_concurrent(A, Some(1));
+
+        let release = Arc::new(Notify::new());
+        let release1 = release.clone();
+        tm.add_task(A, move |_stop| async move {
+            release1.notified().await;
+            "first".to_string()
+        });
+        tm.add_task(A, |_stop| async { "second".to_string() });
+
+        // Give the spawned tasks a chance to reach their await points: the
+        // first acquires its permit immediately, the second queues behind it.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(tm.num_tasks(&A), 2);
+        assert_eq!(tm.num_running(&A), 1);
+        assert_eq!(tm.num_queued(&A), 1);
+
+        release.notify_one();
+
+        assert_eq!(outcomes.next().await.unwrap(), (A, "first".to_string()));
+        assert_eq!(outcomes.next().await.unwrap(), (A, "second".to_string()));
+        assert_eq!(tm.num_running(&A), 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_abandons_queued_acquire_on_stop() {
+        use GroupKey::*;
+
+        let (tx, mut outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |_| None);
+
+        tm.set_max_concurrent(A, Some(1));
+
+        tm.add_task(A, |stop| blocker("holder", stop));
+        // Queues behind the permit held by "holder", and never runs.
+        tm.add_task(A, |_stop| async { "never".to_string() });
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(tm.num_queued(&A), 1);
+
+        tm.stop_group(&A).await;
+
+        assert_eq!(outcomes.next().await.unwrap(), (A, "holder".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_add_group_and_reparent_single_node() {
+        use GroupKey::*;
+
+        let (tx, mut outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |_| None);
+
+        tm.add_group(A, None).unwrap();
+        tm.add_group(B, Some(A)).unwrap();
+        tm.add_group(D, Some(B)).unwrap();
+        assert_eq!(tm.descendants(&A), hashset! {A, B, D});
+
+        // Move D out from under B to hang directly off A.
+        tm.reparent(D, Some(A)).unwrap();
+        assert_eq!(tm.descendants(&A), hashset! {A, B, D});
+        assert_eq!(tm.descendants(&B), hashset! {B});
+
+        tm.add_task(D, |stop| blocker("d1", stop));
+        tm.stop_group(&B).await;
+        assert_eq!(tm.num_tasks(&D), 1);
+
+        tm.stop_group(&A).await;
+        assert_eq!(outcomes.next().await.unwrap(), (D, "d1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_reparent_moves_entire_subtree() {
+        use GroupKey::*;
+
+        let (tx, mut outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |_| None);
+
+        tm.add_group(A, None).unwrap();
+        tm.add_group(B, Some(A)).unwrap();
+        tm.add_group(D, Some(B)).unwrap();
+        tm.add_task(D, |stop| blocker("d1", stop));
+
+        // Detach the whole B/D branch from A.
+        tm.reparent(B, None).unwrap();
+        assert_eq!(tm.descendants(&A), hashset! {A});
+        assert_eq!(tm.descendants(&B), hashset! {B, D});
+
+        // A no longer tears down D's task...
+        tm.stop_group(&A).await;
+        assert_eq!(tm.num_tasks(&D), 1);
+
+        // ...but B, the new root of the branch, still does.
+        tm.stop_group(&B).await;
+        assert_eq!(outcomes.next().await.unwrap(), (D, "d1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_reparent_rejects_cycle() {
+        use GroupKey::*;
+
+        let (tx, _outcomes) = mpsc::channel(4);
+        let mut tm: TaskManager<GroupKey, String> = TaskManager::new(tx, |_| None);
+
+        tm.add_group(A, None).unwrap();
+        tm.add_group(B, Some(A)).unwrap();
+        tm.add_group(D, Some(B)).unwrap();
+
+        assert_eq!(tm.reparent(A, Some(D)), Err(ReparentError::Cycle));
+        assert_eq!(tm.reparent(A, Some(A)), Err(ReparentError::Cycle));
+
+        // Topology is unchanged after a rejected reparent.
+        assert_eq!(tm.descendants(&A), hashset! {A, B, D});
+        assert_eq!(tm.descendants(&B), hashset! {B, D});
+    }
 }
@@ -10,14 +10,14 @@ use std::{
     ops::{Deref, DerefMut},
     pin::Pin,
     sync::{
-        atomic::{AtomicI32, AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering},
         Arc,
     },
     task::{Context, Poll, Waker},
 };
 
 use futures::{stream::FuturesUnordered, Future, FutureExt};
-use tokio::sync::{broadcast, oneshot, OwnedSemaphorePermit, Semaphore};
+use tokio::sync::{broadcast, oneshot, Notify, OwnedSemaphorePermit, Semaphore};
 
 use broadcast::error::TryRecvError;
 
@@ -27,6 +27,8 @@ pub struct StopBroadcaster {
     sem: Arc<Semaphore>,
     num: Arc<AtomicU32>,
     waker: Option<Waker>,
+    stopped: Arc<AtomicBool>,
+    notify: Arc<Notify>,
 }
 
 impl StopBroadcaster {
@@ -37,6 +39,8 @@ impl StopBroadcaster {
             sem: Arc::new(Semaphore::new(0)),
             num: Arc::new(0.into()),
             waker: None,
+            stopped: Arc::new(AtomicBool::new(false)),
+            notify: Arc::new(Notify::new()),
         }
     }
 
@@ -54,9 +58,22 @@ impl StopBroadcaster {
         }
     }
 
+    /// A cloneable, poll-friendly handle on this broadcaster's stop signal.
+    /// Unlike [`StopListener`], obtaining a token does not register it with
+    /// the permit/semaphore accounting used to detect when a group has fully
+    /// drained, so tokens can be held indefinitely without blocking shutdown.
+    pub fn token(&self) -> StopToken {
+        StopToken {
+            stopped: self.stopped.clone(),
+            notify: self.notify.clone(),
+        }
+    }
+
     pub fn emit(&mut self) {
         // If a receiver is dropped, we don't care.
         dbg!("emit");
+        self.stopped.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
         self.tx.send(self.waker.clone()).ok();
     }
 
@@ -65,6 +82,40 @@ impl StopBroadcaster {
     }
 }
 
+/// A cheaply cloneable, poll-based handle on a group's stop signal, for tasks
+/// that want to check for cancellation between iterations of their own work
+/// loop rather than `select`ing on a [`StopListener`]. Because `stop_group`
+/// signals every descendant group's own broadcaster directly, a token
+/// obtained for a given group already observes cancellation from any of its
+/// ancestors, matching the hierarchical `descendants`/`parent_map` semantics.
+#[derive(Clone)]
+pub struct StopToken {
+    stopped: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl StopToken {
+    /// Whether the group has been stopped, without awaiting anything.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the group has been stopped.
+    pub async fn cancelled(&self) {
+        loop {
+            // Registering interest before checking the flag (rather than
+            // after) is what makes this race-free: `Notify` guarantees a
+            // `notified()` future observes any `notify_waiters()` call that
+            // happens after it was created, even if woken before it's polled.
+            let notified = self.notify.notified();
+            if self.is_stopped() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
 impl Future for StopBroadcaster {
     type Output = ();
 